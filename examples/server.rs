@@ -61,8 +61,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
 
+    // Tie the profiler's lifetime to ctrl-c: on signal we notify `serve()`,
+    // which stops accepting, drops queued connections and returns; it does
+    // not wait for connections that are already being served.
+    let (shutdown_tx, shutdown_rx) = async_channel::bounded::<()>(1);
+    task::spawn(async move {
+        let _ = tokio::signal::ctrl_c().await;
+        let _ = shutdown_tx.send(()).await;
+    });
+
     let t2: JoinHandle<_> = task::spawn(async move {
-        pprof_hyper_server::serve(cli.pprof.bind_address, Config::default())
+        pprof_hyper_server::serve(cli.pprof.bind_address, Config::default(), Some(shutdown_rx))
             .await
             .unwrap();
     });
@@ -1,12 +1,16 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
-#![forbid(unsafe_code)]
+// The `rusage` feature needs a single `libc::getrusage` call, so unsafe is
+// merely denied (and opted back in, narrowly) there; it stays forbidden
+// everywhere else.
+#![cfg_attr(not(feature = "rusage"), forbid(unsafe_code))]
+#![cfg_attr(feature = "rusage", deny(unsafe_code))]
 #![warn(missing_docs)]
 #![warn(clippy::print_stderr)]
 #![warn(clippy::print_stdout)]
 
 use anyhow::Result;
-use async_channel::bounded;
+use async_channel::{Receiver, bounded};
 use async_io::Async;
 use futures_lite::future;
 use http_body_util::Full;
@@ -18,16 +22,30 @@ use hyper::{
 use smol_hyper::rt::FuturesIo;
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{HashMap, hash_map::Entry},
+    future::Future,
     net::{SocketAddr, TcpListener, TcpStream},
-    sync::Arc,
+    sync::{Arc, Mutex, OnceLock, Weak},
 };
 
 const MAX_CONCURRENT_REQUESTS: usize = 2; // 1 cpu + 1 mem
 const NOT_FOUND: &[u8] = "Not Found".as_bytes();
 
+/// Authorization hook signature: given the incoming request, decide whether
+/// profiling should proceed.
+pub type AuthorizeFn = Arc<dyn Fn(&Request<Incoming>) -> Authorization + Send + Sync>;
+
+/// Outcome of a [`Config::authorize`] hook.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Authorization {
+    /// Proceed with the request.
+    Allow,
+    /// Reject the request with the given status (typically `401` or `403`).
+    Reject(StatusCode),
+}
+
 /// Config allows customizing global pprof config.
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone)]
 pub struct Config<'a> {
     /// Defaults to pprof_cpu::PPROF_BLOCKLIST.
     pub pprof_blocklist: Option<&'a [&'a str]>,
@@ -35,6 +53,96 @@ pub struct Config<'a> {
     pub pprof_default_seconds: Option<i32>,
     /// Defaults to pprof_cpu::PPROF_DEFAULT_SAMPLING.
     pub pprof_default_sampling: Option<i32>,
+    /// When set, each accepted connection is TLS-terminated before it is
+    /// served, so the pprof endpoints can be exposed on an encrypted port.
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsIdentity>,
+    /// HTTP protocol versions to serve. Defaults to [`HttpVersion::Http1`],
+    /// preserving the original HTTP/1.1-only behavior.
+    pub http_version: HttpVersion,
+    /// Optional hook invoked before every request is dispatched; a reject
+    /// short-circuits with the returned status instead of running a capture.
+    /// See [`Config::bearer_token`] for a ready-made checker.
+    pub authorize: Option<AuthorizeFn>,
+}
+
+// Hand-rolled because `authorize` holds a closure, which is not `Debug`.
+impl std::fmt::Debug for Config<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut s = f.debug_struct("Config");
+        s.field("pprof_blocklist", &self.pprof_blocklist)
+            .field("pprof_default_seconds", &self.pprof_default_seconds)
+            .field("pprof_default_sampling", &self.pprof_default_sampling);
+        #[cfg(feature = "tls")]
+        s.field("tls", &self.tls);
+        s.field("http_version", &self.http_version)
+            .field("authorize", &self.authorize.as_ref().map(|_| "<fn>"))
+            .finish()
+    }
+}
+
+/// The actual decision behind [`Config::bearer_token`], pulled out of the
+/// closure so it can be exercised directly with a plain `HeaderMap` instead of
+/// a full `Request<Incoming>`.
+fn check_bearer_token(headers: &hyper::HeaderMap, secret: &str) -> Authorization {
+    let provided = headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if constant_time_eq(token.as_bytes(), secret.as_bytes()) => {
+            Authorization::Allow
+        }
+        _ => Authorization::Reject(StatusCode::UNAUTHORIZED),
+    }
+}
+
+impl Config<'_> {
+    /// Build an authorization hook requiring an `Authorization: Bearer <token>`
+    /// header whose token matches `secret`, compared in constant time. Requests
+    /// without a matching token are rejected with `401 Unauthorized`.
+    pub fn bearer_token(secret: impl Into<String>) -> AuthorizeFn {
+        let secret = secret.into();
+        Arc::new(move |req: &Request<Incoming>| check_bearer_token(req.headers(), &secret))
+    }
+}
+
+/// Selects which HTTP protocol versions [`serve()`] will speak on each
+/// connection. Without the `http2` feature, every connection is served as
+/// HTTP/1.1 whichever variant is selected.
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// HTTP/1.1 only.
+    #[default]
+    Http1,
+    /// HTTP/2 only, via h2c prior-knowledge.
+    Http2,
+    /// Negotiate either HTTP/1.1 or h2c prior-knowledge automatically.
+    Auto,
+}
+
+/// TLS server identity used to terminate connections: a PKCS#12 archive
+/// (certificate chain plus private key) and the password protecting it.
+#[cfg(feature = "tls")]
+#[derive(Clone)]
+pub struct TlsIdentity {
+    /// DER-encoded PKCS#12 archive.
+    pub pkcs12: Vec<u8>,
+    /// Password protecting the archive.
+    pub password: String,
+}
+
+// Hand-rolled so the archive bytes and password never leak through `Config`'s
+// `Debug`.
+#[cfg(feature = "tls")]
+impl std::fmt::Debug for TlsIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsIdentity")
+            .field("pkcs12", &format_args!("<{} bytes>", self.pkcs12.len()))
+            .field("password", &"<redacted>")
+            .finish()
+    }
 }
 
 #[cfg(all(feature = "pprof_cpu", not(target_env = "msvc")))]
@@ -44,41 +152,385 @@ mod pprof_cpu {
     pub const PPROF_DEFAULT_SAMPLING: i32 = 99;
 }
 
-struct Task<'a> {
-    client: Async<TcpStream>,
-    config: Arc<Config<'a>>,
+#[cfg(all(feature = "rusage", not(target_env = "msvc")))]
+mod rusage {
+    use async_io::Timer;
+    use futures_lite::future;
+    use std::time::Duration;
+
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Process memory statistics sampled over a profiling window.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct RusageStats {
+        /// Maximum resident set size observed, in bytes.
+        pub max_rss_bytes: u64,
+        /// Growth from the first sample to the peak, in bytes.
+        pub rss_delta_bytes: i64,
+    }
+
+    /// Current `ru_maxrss` for this process, normalised to bytes.
+    #[allow(unsafe_code)]
+    pub fn current_max_rss_bytes() -> u64 {
+        // SAFETY: getrusage only writes into `usage`; RUSAGE_SELF is always valid.
+        let usage = unsafe {
+            let mut usage: libc::rusage = std::mem::zeroed();
+            libc::getrusage(libc::RUSAGE_SELF, &mut usage);
+            usage
+        };
+
+        let max_rss = usage.ru_maxrss as u64;
+        // Linux reports ru_maxrss in kilobytes; macOS already reports bytes.
+        #[cfg(target_os = "macos")]
+        {
+            max_rss
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            max_rss.saturating_mul(1024)
+        }
+    }
+
+    /// Sleep for `window` while polling memory every [`POLL_INTERVAL`], returning
+    /// the peak RSS observed and its growth relative to the first sample. This
+    /// replaces the plain `Timer::after` sleep so the profiling window is
+    /// unchanged.
+    pub async fn poll_during(window: Duration) -> RusageStats {
+        let first = current_max_rss_bytes();
+        let mut max = first;
+
+        future::or(
+            async {
+                Timer::after(window).await;
+            },
+            async {
+                loop {
+                    Timer::after(POLL_INTERVAL).await;
+                    max = max.max(current_max_rss_bytes());
+                }
+            },
+        )
+        .await;
+
+        RusageStats {
+            max_rss_bytes: max,
+            rss_delta_bytes: max as i64 - first as i64,
+        }
+    }
 }
 
-impl Task<'_> {
-    /// Handle a new client.
-    async fn handle_client(self) -> Result<()> {
-        hyper::server::conn::http1::Builder::new()
-            .serve_connection(
-                FuturesIo::new(&self.client),
-                service_fn(|req| self.serve(req)),
-            )
-            .await
-            .unwrap_or_default(); // don't use ? otherwise early connection close errors are propagated
+/// Identifies a profiling run so that concurrent callers asking for the same
+/// thing can share a single capture instead of each starting their own.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ProfileKey {
+    endpoint: &'static str,
+    seconds: i32,
+    sampling: i32,
+}
 
-        Ok(())
+/// The payload produced by a single profiling run: the encoded profile
+/// bytes, plus (when the `rusage` feature is enabled) the RSS stats sampled
+/// over the same capture window. Bundling both together means every caller
+/// coalesced onto the same [`Flight`] — leader and followers alike — reports
+/// identical headers, rather than only the leader seeing the stats it
+/// happened to collect.
+#[derive(Clone)]
+struct ProfileOutput {
+    bytes: Bytes,
+    #[cfg(all(feature = "rusage", not(target_env = "msvc")))]
+    rusage: Option<rusage::RusageStats>,
+}
+
+/// A profiling run that is currently in flight. The leader drops `signal`'s
+/// sender once the capture finishes; followers awaiting `signal.recv()` then
+/// read the produced profile from `result`.
+struct Flight {
+    result: OnceLock<Arc<ProfileOutput>>,
+    signal: Receiver<()>,
+}
+
+/// Shared registry of in-flight profiling runs, keyed by [`ProfileKey`], so a
+/// thundering herd of scrape clients collapses into one capture plus fan-out.
+type InflightMap = Arc<Mutex<HashMap<ProfileKey, Weak<Flight>>>>;
+
+/// Drives hyper's HTTP/2 background work on the smol global executor, mirroring
+/// the async-io/async-global-executor runtime the rest of the server uses.
+#[cfg(feature = "http2")]
+#[derive(Clone)]
+struct SmolExecutor;
+
+#[cfg(feature = "http2")]
+impl<F> hyper::rt::Executor<F> for SmolExecutor
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, fut: F) {
+        async_global_executor::spawn(fut).detach();
     }
+}
 
+/// Everything needed to answer a single request, independent of the
+/// connection it arrived on. Every field is an `Arc`, so cloning a `Handler`
+/// is cheap.
+///
+/// This is split out from [`Task`] (which additionally owns the raw client
+/// socket) because HTTP/2's codec hands the per-stream service to
+/// [`SmolExecutor`], and `hyper::rt::Executor::execute` requires `F: Future +
+/// Send + 'static` — a service built from `&Task` would borrow the
+/// connection's stack frame and could never satisfy that. A cloned `Handler`
+/// owns its data instead of borrowing it, so it can be moved into the
+/// service closure and satisfy `'static` on its own.
+#[derive(Clone)]
+struct Handler<'a> {
+    config: Arc<Config<'a>>,
+    inflight: InflightMap,
+}
+
+impl Handler<'_> {
     async fn serve(&self, req: Request<Incoming>) -> Result<Response<Full<Bytes>>> {
+        // Gate the expensive, security-sensitive endpoints behind the optional
+        // authorization hook before dispatching to any capture.
+        if let Some(authorize) = &self.config.authorize {
+            if let Some(response) = rejection_response(authorize(&req)) {
+                return Ok(response);
+            }
+        }
+
         match (req.method(), req.uri().path()) {
             (&Method::GET, "/debug/pprof/allocs" | "/debug/pprof/heap") => {
                 self.memory_profile().await
             }
             (&Method::GET, "/debug/pprof/profile") => self.cpu_profile(req).await,
+            (&Method::GET, "/debug/pprof/rusage") => self.rusage().await,
             _ => not_found(),
         }
     }
 }
 
+struct Task<'a> {
+    client: Async<TcpStream>,
+    handler: Handler<'a>,
+    /// Shared acceptor when TLS termination is enabled; the handshake itself is
+    /// performed inside [`Task::handle_client`], off the accept path.
+    #[cfg(feature = "tls")]
+    acceptor: Option<Arc<async_native_tls::TlsAcceptor>>,
+}
+
+/// Handles a connection once HTTP/2 is compiled in, so `serve_io` may need to
+/// pick the http2/auto builders. Their codecs require the service to be
+/// `Send + 'static` (see [`Handler`]'s doc comment), which in turn requires
+/// `Config`'s borrowed data to outlive `'static` — hence the bound here.
+#[cfg(feature = "http2")]
+impl<'a> Task<'a>
+where
+    'a: 'static,
+{
+    /// Handle a new client.
+    async fn handle_client(self) -> Result<()> {
+        // When TLS is configured, perform the handshake here — inside the
+        // spawned task rather than the accept loop — so a slow or malicious
+        // handshake can't stall the listener. Handshake failures drop the
+        // connection the same way connection errors are swallowed below.
+        #[cfg(feature = "tls")]
+        if let Some(acceptor) = self.acceptor.clone() {
+            let stream = match acceptor.accept(&self.client).await {
+                Ok(stream) => stream,
+                Err(_) => return Ok(()),
+            };
+
+            self.serve_io(FuturesIo::new(stream)).await;
+            return Ok(());
+        }
+
+        self.serve_io(FuturesIo::new(&self.client)).await;
+
+        Ok(())
+    }
+
+    /// Serve a single connection over `io`, selecting the protocol builder
+    /// according to [`Config::http_version`]. Connection errors are swallowed
+    /// the same way as the original http1 path (don't use `?`, otherwise early
+    /// connection close errors are propagated).
+    async fn serve_io<I>(&self, io: I)
+    where
+        I: hyper::rt::Read + hyper::rt::Write + Unpin,
+    {
+        match self.handler.config.http_version {
+            HttpVersion::Http2 => {
+                let handler = self.handler.clone();
+                let service = service_fn(move |req| {
+                    let handler = handler.clone();
+                    async move { handler.serve(req).await }
+                });
+                hyper::server::conn::http2::Builder::new(SmolExecutor)
+                    .max_concurrent_streams(MAX_CONCURRENT_REQUESTS as u32)
+                    .serve_connection(io, service)
+                    .await
+                    .unwrap_or_default();
+            }
+            HttpVersion::Auto => {
+                let handler = self.handler.clone();
+                let service = service_fn(move |req| {
+                    let handler = handler.clone();
+                    async move { handler.serve(req).await }
+                });
+                let mut builder = hyper_util::server::conn::auto::Builder::new(SmolExecutor);
+                builder
+                    .http2()
+                    .max_concurrent_streams(MAX_CONCURRENT_REQUESTS as u32);
+                builder
+                    .serve_connection(io, service)
+                    .await
+                    .unwrap_or_default();
+            }
+            HttpVersion::Http1 => {
+                let service = service_fn(|req| self.handler.serve(req));
+                hyper::server::conn::http1::Builder::new()
+                    .serve_connection(io, service)
+                    .await
+                    .unwrap_or_default();
+            }
+        }
+    }
+}
+
+/// Handles a connection when HTTP/2 isn't compiled in: the only builder left
+/// is http1, whose `serve_connection` awaits the service directly rather than
+/// spawning it, so this impl needs no `'a: 'static` bound of its own. (`serve()`
+/// still requires one, because it spawns each connection's task.)
+#[cfg(not(feature = "http2"))]
 impl Task<'_> {
+    /// Handle a new client.
+    async fn handle_client(self) -> Result<()> {
+        // When TLS is configured, perform the handshake here — inside the
+        // spawned task rather than the accept loop — so a slow or malicious
+        // handshake can't stall the listener. Handshake failures drop the
+        // connection the same way connection errors are swallowed below.
+        #[cfg(feature = "tls")]
+        if let Some(acceptor) = self.acceptor.clone() {
+            let stream = match acceptor.accept(&self.client).await {
+                Ok(stream) => stream,
+                Err(_) => return Ok(()),
+            };
+
+            self.serve_io(FuturesIo::new(stream)).await;
+            return Ok(());
+        }
+
+        self.serve_io(FuturesIo::new(&self.client)).await;
+
+        Ok(())
+    }
+
+    /// Serve a single connection over `io`. Connection errors are swallowed
+    /// the same way as the original http1 path (don't use `?`, otherwise
+    /// early connection close errors are propagated).
+    async fn serve_io<I>(&self, io: I)
+    where
+        I: hyper::rt::Read + hyper::rt::Write + Unpin,
+    {
+        let service = service_fn(|req| self.handler.serve(req));
+        hyper::server::conn::http1::Builder::new()
+            .serve_connection(io, service)
+            .await
+            .unwrap_or_default();
+    }
+}
+
+/// Run `run` under single-flight coalescing for `key` against `inflight`: the
+/// first caller for a key performs the capture while later callers await the
+/// same slot and receive a clone of the one produced profile. The entry is
+/// removed once the run completes or errors so subsequent requests start
+/// fresh.
+///
+/// The check for an in-flight run and the registration of a new one happen
+/// under a single lock acquisition (via [`HashMap::entry`]), so two callers
+/// racing on an empty slot can't both conclude they're the leader and both
+/// start a capture. Likewise, the leader only clears the slot it registered
+/// if it's still there under its own identity (compared by pointer, not just
+/// by key) — otherwise a slower leader could evict a newer one's still-live
+/// `Flight` after being superseded.
+#[allow(dead_code)]
+async fn coalesce<F, Fut>(
+    inflight: &InflightMap,
+    key: ProfileKey,
+    run: F,
+) -> Result<Arc<ProfileOutput>>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<ProfileOutput>>,
+{
+    let role = {
+        let mut map = inflight.lock().unwrap();
+        match map.entry(key.clone()) {
+            Entry::Occupied(mut entry) => match entry.get().upgrade() {
+                Some(flight) => Err(flight),
+                None => {
+                    let (tx, rx) = bounded::<()>(1);
+                    let flight = Arc::new(Flight {
+                        result: OnceLock::new(),
+                        signal: rx,
+                    });
+                    entry.insert(Arc::downgrade(&flight));
+                    Ok((flight, tx))
+                }
+            },
+            Entry::Vacant(entry) => {
+                let (tx, rx) = bounded::<()>(1);
+                let flight = Arc::new(Flight {
+                    result: OnceLock::new(),
+                    signal: rx,
+                });
+                entry.insert(Arc::downgrade(&flight));
+                Ok((flight, tx))
+            }
+        }
+    };
+
+    let (flight, tx) = match role {
+        Err(flight) => {
+            // Follower: the leader drops its sender on completion, closing
+            // the channel.
+            let _ = flight.signal.recv().await;
+            return match flight.result.get() {
+                Some(output) => Ok(output.clone()),
+                None => Err(anyhow::anyhow!("coalesced profiling run failed")),
+            };
+        }
+        Ok(leader) => leader,
+    };
+
+    let outcome = run().await;
+
+    // Only clear the slot if it's still ours: another leader may have
+    // replaced our entry by the time we get here, and we must not evict
+    // their still-live flight.
+    {
+        let mut map = inflight.lock().unwrap();
+        if map.get(&key).map(Weak::as_ptr) == Some(Arc::as_ptr(&flight)) {
+            map.remove(&key);
+        }
+    }
+
+    match outcome {
+        Ok(content) => {
+            let output = Arc::new(content);
+            let _ = flight.result.set(output.clone());
+            drop(tx); // wake any followers so they clone the result
+            Ok(output)
+        }
+        Err(err) => {
+            drop(tx); // wake followers so they observe the failure
+            Err(err)
+        }
+    }
+}
+
+impl Handler<'_> {
     #[cfg(all(feature = "pprof_cpu", not(target_env = "msvc")))]
     async fn cpu_profile(&self, req: Request<Incoming>) -> Result<Response<Full<Bytes>>> {
         use crate::pprof_cpu::*;
-        use async_io::Timer;
         use pprof::{ProfilerGuardBuilder, protos::Message};
         use std::time::Duration;
 
@@ -101,19 +553,57 @@ impl Task<'_> {
 
         let blocklist = self.config.pprof_blocklist.unwrap_or(PPROF_BLOCKLIST);
 
-        let guard = ProfilerGuardBuilder::default()
-            .frequency(profile_sampling)
-            .blocklist(blocklist)
-            .build()?;
+        let key = ProfileKey {
+            endpoint: "profile",
+            seconds: profile_seconds,
+            sampling: profile_sampling,
+        };
 
-        Timer::after(Duration::from_secs(profile_seconds.try_into()?)).await;
+        let output = coalesce(&self.inflight, key, || async move {
+            let guard = ProfilerGuardBuilder::default()
+                .frequency(profile_sampling)
+                .blocklist(blocklist)
+                .build()?;
 
-        let profile = guard.report().build()?.pprof()?;
+            #[cfg(all(feature = "rusage", not(target_env = "msvc")))]
+            let rusage =
+                rusage::poll_during(Duration::from_secs(profile_seconds.try_into()?)).await;
+            #[cfg(not(all(feature = "rusage", not(target_env = "msvc"))))]
+            async_io::Timer::after(Duration::from_secs(profile_seconds.try_into()?)).await;
 
-        let mut content = Vec::new();
-        profile.encode(&mut content)?;
+            let profile = guard.report().build()?.pprof()?;
+
+            let mut content = Vec::new();
+            profile.encode(&mut content)?;
+
+            Ok(ProfileOutput {
+                bytes: Bytes::from(content),
+                #[cfg(all(feature = "rusage", not(target_env = "msvc")))]
+                rusage: Some(rusage),
+            })
+        })
+        .await?;
+
+        #[allow(unused_mut)]
+        let mut response = Response::new(Full::new(output.bytes.clone()));
+
+        // Surface the sampled memory stats so scrapers can chart RSS alongside
+        // CPU. `output` is shared across the leader and every coalesced
+        // follower, so they all get the same headers.
+        #[cfg(all(feature = "rusage", not(target_env = "msvc")))]
+        if let Some(stats) = output.rusage {
+            let headers = response.headers_mut();
+            headers.insert(
+                "x-pprof-max-rss-bytes",
+                stats.max_rss_bytes.to_string().parse()?,
+            );
+            headers.insert(
+                "x-pprof-rss-delta-bytes",
+                stats.rss_delta_bytes.to_string().parse()?,
+            );
+        }
 
-        Ok(Response::new(Full::new(Bytes::from(content))))
+        Ok(response)
     }
 
     #[cfg(any(not(feature = "pprof_cpu"), target_env = "msvc"))]
@@ -121,24 +611,55 @@ impl Task<'_> {
         not_found()
     }
 
+    #[cfg(all(feature = "rusage", not(target_env = "msvc")))]
+    async fn rusage(&self) -> Result<Response<Full<Bytes>>> {
+        let max_rss_bytes = rusage::current_max_rss_bytes();
+        let body = format!("{{\"max_rss_bytes\":{max_rss_bytes}}}");
+
+        Ok(Response::builder()
+            .header(hyper::header::CONTENT_TYPE, "application/json")
+            .body(Full::new(Bytes::from(body)))
+            .unwrap_or_default())
+    }
+
+    #[cfg(any(not(feature = "rusage"), target_env = "msvc"))]
+    async fn rusage(&self) -> Result<Response<Full<Bytes>>> {
+        not_found()
+    }
+
     #[cfg(all(feature = "pprof_heap", not(target_env = "msvc")))]
     async fn memory_profile(&self) -> Result<Response<Full<Bytes>>> {
-        let prof_ctl = jemalloc_pprof::PROF_CTL.as_ref();
+        let key = ProfileKey {
+            endpoint: "heap",
+            seconds: 0,
+            sampling: 0,
+        };
 
-        match prof_ctl {
-            None => Err(anyhow::anyhow!("heap profiling not activated")),
-            Some(prof_ctl) => {
-                let mut prof_ctl = prof_ctl.lock().await;
+        let output = coalesce(&self.inflight, key, || async move {
+            let prof_ctl = jemalloc_pprof::PROF_CTL.as_ref();
 
-                if !prof_ctl.activated() {
-                    return Err(anyhow::anyhow!("heap profiling not activated"));
-                }
+            match prof_ctl {
+                None => Err(anyhow::anyhow!("heap profiling not activated")),
+                Some(prof_ctl) => {
+                    let mut prof_ctl = prof_ctl.lock().await;
+
+                    if !prof_ctl.activated() {
+                        return Err(anyhow::anyhow!("heap profiling not activated"));
+                    }
 
-                let pprof = prof_ctl.dump_pprof()?;
+                    let pprof = prof_ctl.dump_pprof()?;
 
-                Ok(Response::new(Full::new(Bytes::from(pprof))))
+                    Ok(ProfileOutput {
+                        bytes: Bytes::from(pprof),
+                        #[cfg(all(feature = "rusage", not(target_env = "msvc")))]
+                        rusage: None,
+                    })
+                }
             }
-        }
+        })
+        .await?;
+
+        Ok(Response::new(Full::new(output.bytes.clone())))
     }
 
     #[cfg(any(not(feature = "pprof_heap"), target_env = "msvc"))]
@@ -169,6 +690,35 @@ fn parse_i32_params<'a>(
         .unwrap_or(default)
 }
 
+/// Length-checked constant-time byte comparison, so token checks don't leak
+/// the secret through response timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+
+    diff == 0
+}
+
+/// Builds the short-circuit response for a rejected [`Authorization`]
+/// decision, or `None` when the request should proceed to dispatch.
+fn rejection_response(decision: Authorization) -> Option<Response<Full<Bytes>>> {
+    match decision {
+        Authorization::Allow => None,
+        Authorization::Reject(status) => Some(
+            Response::builder()
+                .status(status)
+                .body(Full::new(Bytes::new()))
+                .unwrap_or_default(),
+        ),
+    }
+}
+
 fn not_found() -> Result<Response<Full<Bytes>>> {
     Ok(Response::builder()
         .status(StatusCode::NOT_FOUND)
@@ -177,35 +727,206 @@ fn not_found() -> Result<Response<Full<Bytes>>> {
 }
 
 /// Listens for incoming connections and serves them under pprof HTTP API.
-pub async fn serve<'a>(bind_address: SocketAddr, config: Config<'a>) -> Result<()> {
+///
+/// When `shutdown` is provided, a message on (or closing of) the receiver stops
+/// the listener from accepting new connections and returns `Ok(())`. Only
+/// accepting and dequeuing race the shutdown signal; each connection is
+/// handled on its own spawned task, so a capture that's already running —
+/// including the leader of a coalesced group — keeps running to completion
+/// rather than being cancelled by shutdown (or by the next accepted
+/// connection, for that matter). `serve()` itself does not wait for those
+/// spawned tasks before returning, so this isn't a drain either: the process
+/// must stay alive long enough for them to finish on their own if that
+/// matters to the caller. Pass `None` to run until the task is aborted,
+/// matching the original unconditional loop. This lets an application tie the
+/// profiler's lifetime to its own ctrl-c / shutdown path.
+///
+/// `Config`'s borrowed data (e.g. a custom `pprof_blocklist`) must outlive
+/// `'static`: each accepted connection is handled on a task spawned onto the
+/// global executor, which requires that task to be `Send + 'static`. With the
+/// `http2` feature, the owned [`Handler`] cloned for the HTTP/2 and Auto paths
+/// is also handed to hyper's HTTP/2 codec, whose executor requires the same
+/// of the service it spawns. This bound applies regardless of which
+/// [`HttpVersion`] is actually selected or which features are enabled.
+pub async fn serve<'a>(
+    bind_address: SocketAddr,
+    config: Config<'a>,
+    shutdown: Option<Receiver<()>>,
+) -> Result<()>
+where
+    'a: 'static,
+{
     let listener = Async::<TcpListener>::bind(bind_address)?;
     let (s, r) = bounded::<Task>(MAX_CONCURRENT_REQUESTS);
     let config = Arc::new(config);
+    let inflight: InflightMap = Arc::new(Mutex::new(HashMap::new()));
+
+    // Build the TLS acceptor once, up front, so a bad identity fails `serve()`
+    // rather than every connection.
+    #[cfg(feature = "tls")]
+    let acceptor = match &config.tls {
+        Some(identity) => {
+            let parsed =
+                async_native_tls::Identity::from_pkcs12(&identity.pkcs12, &identity.password)?;
+            Some(Arc::new(async_native_tls::TlsAcceptor::new(parsed)?))
+        }
+        None => None,
+    };
 
     loop {
         // stack max MAX_CONCURRENT_REQUESTS requests, prefering stacking than answering to them.
         // if we cannot stack anymore, drop the connection and other pending requests.
         // we don't need a multi threaded server to serve pprof server, but don't want it to be a source of DDOS.
-        future::or(
-            async {
-                // Wait for a new client.
-                let listener = listener.accept().await;
-                if let Ok((client, _)) = listener {
-                    let task = Task {
-                        client,
-                        config: config.clone(),
-                    };
-
-                    // we ignore the potential error as it would mean we should drop the connection if channel is full.
-                    let _ = s.try_send(task);
-                }
-            },
+        // Only `accept` and the dequeue-and-spawn below race `shutdown` here;
+        // `handle_client` itself runs on a spawned task, independent of this
+        // loop, so accepting another connection (or shutting down) can no
+        // longer cancel a capture that's already running.
+        let stop = future::or(
+            future::or(
+                async {
+                    // Wait for a new client.
+                    let listener = listener.accept().await;
+                    if let Ok((client, _)) = listener {
+                        let task = Task {
+                            client,
+                            handler: Handler {
+                                config: config.clone(),
+                                inflight: inflight.clone(),
+                            },
+                            #[cfg(feature = "tls")]
+                            acceptor: acceptor.clone(),
+                        };
+
+                        // we ignore the potential error as it would mean we should drop the connection if channel is full.
+                        let _ = s.try_send(task);
+                    }
+                    false
+                },
+                async {
+                    if let Ok(task) = r.recv().await {
+                        // Spawn rather than await inline: this branch must
+                        // resolve quickly, not once the request finishes,
+                        // otherwise it would still be the "work" side of this
+                        // very race — and an in-flight capture (possibly the
+                        // leader of a coalesced group) would get dropped the
+                        // instant the next connection is accepted.
+                        async_global_executor::spawn(async move {
+                            task.handle_client().await.unwrap_or_default();
+                        })
+                        .detach();
+                    }
+                    false
+                },
+            ),
             async {
-                if let Ok(task) = r.recv().await {
-                    task.handle_client().await.unwrap_or_default();
+                match &shutdown {
+                    // `recv()` resolves on a sent message or a closed channel.
+                    Some(rx) => {
+                        let _ = rx.recv().await;
+                        true
+                    }
+                    // Never resolves: without a signal, run like the original loop.
+                    None => future::pending::<bool>().await,
                 }
             },
         )
         .await;
+
+        if stop {
+            break;
+        }
+    }
+
+    // Stop accepting and drain any queued tasks without serving them.
+    while r.try_recv().is_ok() {}
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn coalesce_runs_once_for_concurrent_callers() {
+        let inflight: InflightMap = Arc::new(Mutex::new(HashMap::new()));
+        let key = ProfileKey {
+            endpoint: "test",
+            seconds: 0,
+            sampling: 0,
+        };
+        let runs = AtomicUsize::new(0);
+
+        let call = || {
+            coalesce(&inflight, key.clone(), || async {
+                runs.fetch_add(1, Ordering::SeqCst);
+                // Yield so the other concurrent caller observes this run as
+                // already in flight instead of also falling through to the
+                // leader path — the race this test exists to rule out.
+                async_io::Timer::after(std::time::Duration::from_millis(20)).await;
+                Ok(ProfileOutput {
+                    bytes: Bytes::from_static(b"profile"),
+                    #[cfg(all(feature = "rusage", not(target_env = "msvc")))]
+                    rusage: None,
+                })
+            })
+        };
+
+        let (a, b) = async_io::block_on(future::zip(call(), call()));
+
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(a.is_ok());
+        assert!(b.is_ok());
+    }
+
+    fn headers_with_authorization(value: &str) -> hyper::HeaderMap {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(hyper::header::AUTHORIZATION, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn bearer_token_allows_matching_token() {
+        let headers = headers_with_authorization("Bearer secret");
+        assert_eq!(check_bearer_token(&headers, "secret"), Authorization::Allow);
+    }
+
+    #[test]
+    fn bearer_token_rejects_mismatched_token() {
+        let headers = headers_with_authorization("Bearer wrong");
+        assert_eq!(
+            check_bearer_token(&headers, "secret"),
+            Authorization::Reject(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn bearer_token_rejects_non_bearer_scheme() {
+        let headers = headers_with_authorization("Basic secret");
+        assert_eq!(
+            check_bearer_token(&headers, "secret"),
+            Authorization::Reject(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn bearer_token_rejects_missing_header() {
+        let headers = hyper::HeaderMap::new();
+        assert_eq!(
+            check_bearer_token(&headers, "secret"),
+            Authorization::Reject(StatusCode::UNAUTHORIZED)
+        );
+    }
+
+    #[test]
+    fn rejection_response_allows_request_through() {
+        assert!(rejection_response(Authorization::Allow).is_none());
+    }
+
+    #[test]
+    fn rejection_response_short_circuits_with_status() {
+        let response = rejection_response(Authorization::Reject(StatusCode::FORBIDDEN)).unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
     }
 }